@@ -15,8 +15,37 @@
 
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::arbitration_dao::{ArbitrationDAO, ArbitrationDAORef};
+
+/// Reason an escrow rejected a ruling pushed through `ArbitrationCallback`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum RulingError {
+    /// The caller is not the agreement's DAO or the milestone was not escalated to it
+    NotAuthorized,
+    /// The escrow could not settle the milestone as ruled
+    SettlementFailed,
+}
+
+/// Callback implemented by escrow contracts that escalate disputes to the DAO
+#[ink::trait_definition]
+pub trait ArbitrationCallback {
+    /// Apply the final ruling for an escalated milestone
+    ///
+    /// An `Err` reverts every change the escrow made while applying the ruling.
+    #[ink(message)]
+    fn apply_ruling(
+        &mut self,
+        agreement_id: u128,
+        milestone_index: u32,
+        winner: ink::primitives::AccountId,
+    ) -> Result<(), RulingError>;
+}
+
 #[ink::contract]
 mod arbitration_dao {
+    use super::ArbitrationCallback;
+    use ink::codegen::TraitCallBuilder;
     use ink::storage::Mapping;
 
     /// Dispute status
@@ -38,6 +67,15 @@ mod arbitration_dao {
         InFavorOfDefendant,
     }
 
+    /// Escrow milestone a dispute was escalated from
+    #[derive(Debug, Clone, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct EscrowLink {
+        pub escrow: AccountId,
+        pub agreement_id: u128,
+        pub milestone_index: u32,
+    }
+
     /// Dispute information
     #[derive(Debug, Clone, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
@@ -54,6 +92,7 @@ mod arbitration_dao {
         pub votes_for_defendant: Balance,
         pub resolution: Option<VoteChoice>,
         pub can_appeal: bool,
+        pub escrow: Option<EscrowLink>,
     }
 
     /// Arbitrator information
@@ -119,6 +158,14 @@ mod arbitration_dao {
         resolution: VoteChoice,
     }
 
+    #[ink(event)]
+    pub struct EscrowRulingRejected {
+        #[ink(topic)]
+        dispute_id: u128,
+        #[ink(topic)]
+        escrow: AccountId,
+    }
+
     #[ink(event)]
     pub struct ArbitratorRegistered {
         #[ink(topic)]
@@ -230,40 +277,29 @@ mod arbitration_dao {
             evidence_uri: ink::prelude::string::String,
         ) -> Result<u128> {
             let caller = self.env().caller();
-            let dispute_id = self.next_dispute_id;
-            self.next_dispute_id = self.next_dispute_id
-                .checked_add(1)
-                .expect("Dispute ID overflow");
-
-            let now = self.env().block_timestamp();
-            let voting_ends_at = now
-                .checked_add(self.voting_period)
-                .expect("Voting period overflow");
+            Ok(self.open_dispute(caller, defendant, description, evidence_uri, None))
+        }
 
-            let dispute = Dispute {
-                dispute_id,
-                claimant: caller,
-                defendant,
-                description,
-                evidence_uri,
-                status: DisputeStatus::Open,
-                created_at: now,
-                voting_ends_at,
-                votes_for_claimant: 0,
-                votes_for_defendant: 0,
-                resolution: None,
-                can_appeal: true,
+        /// Create a dispute on behalf of an escrow contract (caller is the escrow)
+        ///
+        /// The ruling is pushed back to the escrow through `ArbitrationCallback`
+        /// when the dispute is finalized, so escrow disputes cannot be appealed.
+        #[ink(message)]
+        pub fn create_escrow_dispute(
+            &mut self,
+            claimant: AccountId,
+            defendant: AccountId,
+            agreement_id: u128,
+            milestone_index: u32,
+            description: ink::prelude::string::String,
+            evidence_uri: ink::prelude::string::String,
+        ) -> Result<u128> {
+            let escrow = EscrowLink {
+                escrow: self.env().caller(),
+                agreement_id,
+                milestone_index,
             };
-
-            self.disputes.insert(dispute_id, &dispute);
-
-            self.env().emit_event(DisputeCreated {
-                dispute_id,
-                claimant: caller,
-                defendant,
-            });
-
-            Ok(dispute_id)
+            Ok(self.open_dispute(claimant, defendant, description, evidence_uri, Some(escrow)))
         }
 
         /// Start voting period
@@ -391,6 +427,26 @@ mod arbitration_dao {
             dispute.resolution = Some(resolution.clone());
             self.disputes.insert(dispute_id, &dispute);
 
+            // Push the ruling back to the escrow the dispute came from. A rejected
+            // ruling must not keep the dispute open; the escrow has its own fallback.
+            if let Some(link) = &dispute.escrow {
+                let winner = match resolution {
+                    VoteChoice::InFavorOfClaimant => dispute.claimant,
+                    VoteChoice::InFavorOfDefendant => dispute.defendant,
+                };
+                let mut escrow: ink::contract_ref!(ArbitrationCallback) = link.escrow.into();
+                let applied = escrow
+                    .call_mut()
+                    .apply_ruling(link.agreement_id, link.milestone_index, winner)
+                    .try_invoke();
+                if !matches!(applied, Ok(Ok(Ok(())))) {
+                    self.env().emit_event(EscrowRulingRejected {
+                        dispute_id,
+                        escrow: link.escrow,
+                    });
+                }
+            }
+
             self.env().emit_event(DisputeResolved {
                 dispute_id,
                 resolution: resolution.clone(),
@@ -463,6 +519,52 @@ mod arbitration_dao {
                 .map(|a| a.is_active)
                 .unwrap_or(false)
         }
+
+        /// Store a new dispute and emit `DisputeCreated`
+        fn open_dispute(
+            &mut self,
+            claimant: AccountId,
+            defendant: AccountId,
+            description: ink::prelude::string::String,
+            evidence_uri: ink::prelude::string::String,
+            escrow: Option<EscrowLink>,
+        ) -> u128 {
+            let dispute_id = self.next_dispute_id;
+            self.next_dispute_id = self.next_dispute_id
+                .checked_add(1)
+                .expect("Dispute ID overflow");
+
+            let now = self.env().block_timestamp();
+            let voting_ends_at = now
+                .checked_add(self.voting_period)
+                .expect("Voting period overflow");
+
+            let dispute = Dispute {
+                dispute_id,
+                claimant,
+                defendant,
+                description,
+                evidence_uri,
+                status: DisputeStatus::Open,
+                created_at: now,
+                voting_ends_at,
+                votes_for_claimant: 0,
+                votes_for_defendant: 0,
+                resolution: None,
+                can_appeal: escrow.is_none(),
+                escrow,
+            };
+
+            self.disputes.insert(dispute_id, &dispute);
+
+            self.env().emit_event(DisputeCreated {
+                dispute_id,
+                claimant,
+                defendant,
+            });
+
+            dispute_id
+        }
     }
 
     #[cfg(test)]
@@ -517,5 +619,38 @@ mod arbitration_dao {
             assert_eq!(dispute.defendant, accounts.charlie);
             assert_eq!(dispute.status, DisputeStatus::Open);
         }
+
+        #[ink::test]
+        fn create_escrow_dispute_links_escrow() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = ArbitrationDAO::new(
+                accounts.alice,
+                100_000_000_000_000_000_000,
+                7 * 24 * 60 * 60 * 1000,
+                5000,
+            );
+
+            // Escrow contract opens the dispute on behalf of its client
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+
+            let dispute_id = contract.create_escrow_dispute(
+                accounts.bob,
+                accounts.charlie,
+                7,
+                1,
+                "Milestone dispute".into(),
+                "".into(),
+            ).unwrap();
+
+            let dispute = contract.get_dispute(dispute_id).unwrap();
+            assert_eq!(dispute.claimant, accounts.bob);
+            assert_eq!(dispute.defendant, accounts.charlie);
+            assert!(!dispute.can_appeal);
+
+            let link = dispute.escrow.unwrap();
+            assert_eq!(link.escrow, accounts.eve);
+            assert_eq!(link.agreement_id, 7);
+            assert_eq!(link.milestone_index, 1);
+        }
     }
 }
@@ -15,6 +15,10 @@
 
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::professional_registry::{
+    ProfessionalProfile, ProfessionalRegistry, ProfessionalRegistryRef, ProfessionalRole,
+};
+
 #[ink::contract]
 mod professional_registry {
     use ink::storage::Mapping;